@@ -99,6 +99,167 @@ mod tests {
             assert_eq!(config, reconstructed);
         }
     }
+    #[test]
+    fn packed_integer_fields_test() {
+        #[derive(as_bit_mask_derive::AsBitMask, Debug, PartialEq)]
+        pub struct PacketHeader {
+            ready: bool,
+            #[bits(3)]
+            mode: u8,
+            #[bits(12)]
+            counter: u16,
+        }
+
+        let header = PacketHeader {
+            ready: true,
+            mode: 0b101,
+            counter: 0xABC,
+        };
+
+        let bytes = header.as_bytes();
+        let reconstructed = PacketHeader::from_bytes(&bytes);
+        assert_eq!(header, reconstructed);
+
+        for mode in 0u8..8 {
+            for counter in [0u16, 1, 0xFFF, 0x123] {
+                let header = PacketHeader { ready: false, mode, counter };
+                let reconstructed = PacketHeader::from_bytes(&header.as_bytes());
+                assert_eq!(header, reconstructed);
+            }
+        }
+    }
+
+    #[test]
+    fn packed_enum_field_test() {
+        #[derive(as_bit_mask_derive::AsBitMaskEnum, Debug, PartialEq, Clone, Copy)]
+        pub enum Mode {
+            Idle = 0,
+            Running = 1,
+            Paused = 2,
+            Stopped = 3,
+        }
+
+        #[derive(as_bit_mask_derive::AsBitMask, Debug, PartialEq)]
+        pub struct Register {
+            enabled: bool,
+            #[bits(2)]
+            mode: Mode,
+        }
+
+        for mode in [Mode::Idle, Mode::Running, Mode::Paused, Mode::Stopped] {
+            for enabled in [true, false] {
+                let reg = Register { enabled, mode };
+                let reconstructed = Register::from_bytes(&reg.as_bytes());
+                assert_eq!(reg, reconstructed);
+            }
+        }
+    }
+
+    #[test]
+    fn byte_aligned_test() {
+        #[derive(as_bit_mask_derive::AsBitMask, Debug, PartialEq)]
+        #[byte_aligned]
+        pub struct PackedStatus {
+            ready: bool,
+            fault: bool,
+            #[bits(6)]
+            code: u8,
+        }
+
+        let status = PackedStatus { ready: true, fault: false, code: 0x2A };
+        let bytes = status.as_bytes();
+        assert_eq!(bytes.len(), 1);
+        assert_eq!(PackedStatus::from_bytes(&bytes), status);
+    }
+
+    #[test]
+    fn msb0_bit_order_test() {
+        #[derive(as_bit_mask_derive::AsBitMask, Debug, PartialEq)]
+        #[bit_order(msb0)]
+        pub struct MsbFirst {
+            a: bool,
+            b: bool,
+            c: bool,
+        }
+
+        let config = MsbFirst { a: true, b: false, c: false };
+        assert_eq!(config.as_bytes(), [0b1000_0000]);
+        assert_eq!(MsbFirst::from_bytes(&[0b1000_0000]), config);
+    }
+
+    #[test]
+    fn be_byte_order_test() {
+        #[derive(as_bit_mask_derive::AsBitMask, Debug, PartialEq)]
+        #[byte_order(be)]
+        pub struct BigEndianRegister {
+            #[bits(12)]
+            counter: u16,
+        }
+
+        for counter in [0u16, 1, 0xABC, 0xFFF] {
+            let reg = BigEndianRegister { counter };
+            let reconstructed = BigEndianRegister::from_bytes(&reg.as_bytes());
+            assert_eq!(reg, reconstructed);
+        }
+    }
+
+    #[test]
+    fn secret_enum_field_test() {
+        #[derive(as_bit_mask_derive::AsBitMaskEnum, Debug, PartialEq, Clone, Copy)]
+        #[secret]
+        pub enum Mode {
+            Idle = 0,
+            Running = 1,
+            Paused = 2,
+            Stopped = 3,
+        }
+
+        #[derive(as_bit_mask_derive::AsBitMask, Debug, PartialEq)]
+        pub struct Register {
+            enabled: bool,
+            #[bits(2)]
+            mode: Mode,
+        }
+
+        for mode in [Mode::Idle, Mode::Running, Mode::Paused, Mode::Stopped] {
+            for enabled in [true, false] {
+                let reg = Register { enabled, mode };
+                let reconstructed = Register::from_bytes(&reg.as_bytes());
+                assert_eq!(reg, reconstructed);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "secret")]
+    fn secret_struct_zeroize_test() {
+        #[derive(as_bit_mask_derive::AsBitMaskEnum, Debug, PartialEq, Clone, Copy)]
+        #[secret]
+        pub enum Mode {
+            Idle = 0,
+            Running = 1,
+            Paused = 2,
+            Stopped = 3,
+        }
+
+        #[derive(as_bit_mask_derive::AsBitMask, Debug, PartialEq)]
+        #[secret]
+        pub struct SecretRegister {
+            enabled: bool,
+            #[bits(2)]
+            mode: Mode,
+            #[bits(5)]
+            key: u8,
+        }
+
+        let mut reg = SecretRegister { enabled: true, mode: Mode::Paused, key: 0x15 };
+        assert_ne!(reg.as_bytes(), [0u8]);
+
+        zeroize::Zeroize::zeroize(&mut reg);
+        assert_eq!(reg.as_bytes(), [0u8]);
+        assert_eq!(reg, SecretRegister { enabled: false, mode: Mode::Idle, key: 0 });
+    }
+
     #[test]
     fn basic_as_bits_test() {
         #[derive(as_bit_mask_derive::AsBits, Debug, PartialEq)]
@@ -1,83 +1,509 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
 
-use syn::{parse_macro_input, Data, DeriveInput, Fields};
+use std::collections::HashMap;
+
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, FieldsNamed, Type};
 use quote::quote;
 
-/// Automatically implements the AsBitMask trait for structs with boolean fields.
+/// How a field's value is converted to and from its packed bits.
+#[derive(PartialEq)]
+enum FieldKind {
+    /// A plain `bool`, stored as a single bit.
+    Bool,
+    /// A primitive integer type, packed/unpacked with direct shifts.
+    Integer,
+    /// A user type (e.g. one deriving `AsBitMaskEnum`) packed/unpacked
+    /// through its own `to_bits`/`from_bits` methods.
+    BitEnum,
+}
+
+/// A single packed field: its name, its declared type, and the number of
+/// bits it occupies in the mask (1 for `bool`, the `#[bits(n)]` value for
+/// everything else).
+struct PackedField<'a> {
+    ident: &'a syn::Ident,
+    ty: &'a Type,
+    width: usize,
+    kind: FieldKind,
+}
+
+fn is_bool_type(ty: &Type) -> bool {
+    matches!(ty, Type::Path(type_path) if type_path.path.is_ident("bool"))
+}
+
+// Bit width of each primitive integer type `#[bits(n)]` can be used on.
+// `usize`/`isize` are assumed to be the common 64-bit case.
+const PRIMITIVE_INT_TYPES: &[(&str, usize)] = &[
+    ("u8", 8), ("u16", 16), ("u32", 32), ("u64", 64), ("u128", 128), ("usize", 64),
+    ("i8", 8), ("i16", 16), ("i32", 32), ("i64", 64), ("i128", 128), ("isize", 64),
+];
+
+fn is_primitive_int_type(ty: &Type) -> bool {
+    matches!(ty, Type::Path(type_path) if PRIMITIVE_INT_TYPES.iter().any(|(name, _)| type_path.path.is_ident(name)))
+}
+
+/// The bit width of a primitive integer type, or `None` if `ty` isn't one
+/// (e.g. it's a bit-enum field, whose width is validated separately).
+fn primitive_int_bit_width(ty: &Type) -> Option<usize> {
+    match ty {
+        Type::Path(type_path) => PRIMITIVE_INT_TYPES
+            .iter()
+            .find(|(name, _)| type_path.path.is_ident(name))
+            .map(|(_, width)| *width),
+        _ => None,
+    }
+}
+
+fn field_kind(ty: &Type) -> FieldKind {
+    if is_bool_type(ty) {
+        FieldKind::Bool
+    } else if is_primitive_int_type(ty) {
+        FieldKind::Integer
+    } else {
+        FieldKind::BitEnum
+    }
+}
+
+/// Determine how many bits a field occupies: `bool` is always 1 bit, any
+/// other type must carry a `#[bits(n)]` attribute naming its width (for an
+/// enum field this must match the companion `AsBitMaskEnum`'s `BITS`, which
+/// is checked separately once the field's kind is known). A primitive
+/// integer's width must fit in the type itself.
+fn field_width(field: &syn::Field) -> syn::Result<usize> {
+    if is_bool_type(&field.ty) {
+        return Ok(1);
+    }
+
+    for attr in &field.attrs {
+        if attr.path().is_ident("bits") {
+            let width_lit = attr.parse_args::<syn::LitInt>()?;
+            let width = width_lit.base10_parse::<usize>()?;
+
+            if let Some(max_width) = primitive_int_bit_width(&field.ty) {
+                if width > max_width {
+                    return Err(Error::new_spanned(
+                        &width_lit,
+                        format!(
+                            "#[bits({})] on field '{}' exceeds the {} bits available in its type",
+                            width, field.ident.as_ref().unwrap(), max_width
+                        ),
+                    ));
+                }
+            }
+
+            return Ok(width);
+        }
+    }
+
+    Err(Error::new_spanned(
+        field,
+        format!(
+            "field '{}' is not a bool and is missing a #[bits(n)] attribute",
+            field.ident.as_ref().unwrap()
+        ),
+    ))
+}
+
+/// The narrowest unsigned integer type that can hold a value of the given
+/// bit width, used for the intermediate accumulator when unpacking
+/// bit-enum fields and as the raw type of `AsBitMaskEnum`'s
+/// `to_bits`/`from_bits`. Integer fields accumulate in their own type.
+fn uint_type_for_width(width: usize) -> proc_macro2::TokenStream {
+    if width <= 8 {
+        quote! { u8 }
+    } else if width <= 16 {
+        quote! { u16 }
+    } else if width <= 32 {
+        quote! { u32 }
+    } else if width <= 64 {
+        quote! { u64 }
+    } else {
+        quote! { u128 }
+    }
+}
+
+/// Check that `input` is a struct with no generic parameters, and return
+/// its named fields. Used by every derive in this crate that operates on
+/// struct fields.
+fn require_named_struct<'a>(input: &'a DeriveInput, macro_name: &str) -> syn::Result<&'a FieldsNamed> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            format!("{} derive does not support generic parameters", macro_name),
+        ));
+    }
+
+    match &input.data {
+        Data::Struct(data_struct) => match &data_struct.fields {
+            Fields::Named(fields_named) => Ok(fields_named),
+            other => Err(Error::new_spanned(
+                other,
+                format!("{} derive only supports structs with named fields", macro_name),
+            )),
+        },
+        _ => Err(Error::new_spanned(
+            &input.ident,
+            format!("{} derive only supports structs", macro_name),
+        )),
+    }
+}
+
+/// Parse the `#[index(n)]` attribute on a field.
+fn parse_index_attr(field: &syn::Field) -> syn::Result<usize> {
+    for attr in &field.attrs {
+        if attr.path().is_ident("index") {
+            let index_value = attr.parse_args::<syn::LitInt>()?;
+            return index_value.base10_parse::<usize>();
+        }
+    }
+
+    Err(Error::new_spanned(
+        field,
+        format!(
+            "field '{}' is missing #[index(n)] attribute",
+            field.ident.as_ref().unwrap()
+        ),
+    ))
+}
+
+/// Collect each named field's explicit `#[index(n)]`, rejecting two fields
+/// that claim the same bit.
+fn collect_indexed_fields(fields: &FieldsNamed) -> syn::Result<Vec<(&syn::Ident, usize)>> {
+    let mut field_data = Vec::new();
+    let mut seen_at: HashMap<usize, &syn::Ident> = HashMap::new();
+
+    for field in &fields.named {
+        let Some(ident) = &field.ident else { continue };
+        let idx = parse_index_attr(field)?;
+
+        if let Some(prev_ident) = seen_at.get(&idx) {
+            return Err(Error::new_spanned(
+                field,
+                format!(
+                    "bit index {} on field '{}' is already used by field '{}'",
+                    idx, ident, prev_ident
+                ),
+            ));
+        }
+        seen_at.insert(idx, ident);
+        field_data.push((ident, idx));
+    }
+
+    Ok(field_data)
+}
+
+fn has_unit_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident(name))
+}
+
+/// When `#[byte_aligned]` is present on the struct, check that the occupied
+/// bits fill whole bytes exactly, rather than silently padding.
+fn check_byte_aligned(input: &DeriveInput, occupied_bits: usize) -> syn::Result<()> {
+    if !has_unit_attr(&input.attrs, "byte_aligned") {
+        return Ok(());
+    }
+
+    let remainder = occupied_bits % 8;
+    if remainder != 0 {
+        let missing_bits = 8 - remainder;
+        return Err(Error::new_spanned(
+            &input.ident,
+            format!(
+                "#[byte_aligned] requires a whole number of bytes, but '{}' occupies {} bits ({} bits short of the next byte boundary)",
+                input.ident, occupied_bits, missing_bits
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Numbering of bits within a byte: `lsb0` (the default) places logical bit
+/// 0 at the least-significant bit, `msb0` at the most-significant.
+enum BitOrder {
+    Lsb0,
+    Msb0,
+}
+
+/// Ordering of bytes within the output array: `le` (the default) keeps the
+/// first logical byte first, `be` reverses the array.
+enum ByteOrder {
+    Le,
+    Be,
+}
+
+/// Parse a container-level `#[bit_order(lsb0|msb0)]` attribute, defaulting
+/// to `lsb0` when absent.
+fn parse_bit_order(attrs: &[syn::Attribute]) -> syn::Result<BitOrder> {
+    for attr in attrs {
+        if attr.path().is_ident("bit_order") {
+            let ident: syn::Ident = attr.parse_args()?;
+            return match ident.to_string().as_str() {
+                "lsb0" => Ok(BitOrder::Lsb0),
+                "msb0" => Ok(BitOrder::Msb0),
+                _ => Err(Error::new_spanned(ident, "#[bit_order(..)] must be `lsb0` or `msb0`")),
+            };
+        }
+    }
+    Ok(BitOrder::Lsb0)
+}
+
+/// Parse a container-level `#[byte_order(le|be)]` attribute, defaulting to
+/// `le` when absent.
+fn parse_byte_order(attrs: &[syn::Attribute]) -> syn::Result<ByteOrder> {
+    for attr in attrs {
+        if attr.path().is_ident("byte_order") {
+            let ident: syn::Ident = attr.parse_args()?;
+            return match ident.to_string().as_str() {
+                "le" => Ok(ByteOrder::Le),
+                "be" => Ok(ByteOrder::Be),
+                _ => Err(Error::new_spanned(ident, "#[byte_order(..)] must be `le` or `be`")),
+            };
+        }
+    }
+    Ok(ByteOrder::Le)
+}
+
+/// Map a logical bit index (as if bits were lsb0-within-byte and bytes were
+/// little-endian) to where it actually lands given the container's
+/// `bit_order`/`byte_order`. `as_bytes` and `from_bytes` both go through
+/// this so round-tripping holds regardless of the chosen ordering.
+fn physical_location(bit: usize, num_bytes: usize, bit_order: &BitOrder, byte_order: &ByteOrder) -> (usize, usize) {
+    let logical_byte = bit / 8;
+    let bit_pos = bit % 8;
+
+    let byte_index = match byte_order {
+        ByteOrder::Le => logical_byte,
+        ByteOrder::Be => num_bytes - 1 - logical_byte,
+    };
+    let physical_bit_pos = match bit_order {
+        BitOrder::Lsb0 => bit_pos,
+        BitOrder::Msb0 => 7 - bit_pos,
+    };
+
+    (byte_index, physical_bit_pos)
+}
+
+/// Automatically implements the AsBitMask trait for structs with boolean and
+/// fixed-width integer fields.
 ///
 /// This macro will generate implementations for:
-/// - `as_bytes`: Converts the boolean fields to a byte array representation
+/// - `as_bytes`: Converts the fields to a byte array representation
 /// - `from_bytes`: Constructs the struct from a byte array representation
 ///
-/// The number of bytes in the array is calculated based on the number of fields.
-#[proc_macro_derive(AsBitMask)]
+/// `bool` fields occupy a single bit. A primitive integer field must be
+/// annotated with `#[bits(n)]` giving its width in bits (e.g.
+/// `#[bits(3)] speed: u8`), and must satisfy `n <= 8 * size_of::<T>()`. A
+/// field whose type derives `AsBitMaskEnum` is also annotated with
+/// `#[bits(n)]`, where `n` matches that enum's `BITS`, and is packed
+/// through its `to_bits`/`from_bits` methods rather than raw shifts.
+/// Fields are packed in
+/// declaration order, LSB-first, and may straddle byte boundaries. The
+/// number of bytes in the array is `(total_bits + 7) / 8`.
+///
+/// An opt-in `#[byte_aligned]` on the struct rejects a layout that doesn't
+/// fill its bytes exactly, instead of silently padding with dead bits.
+///
+/// By default bits are numbered lsb0-within-byte and bytes are laid out
+/// little-endian. `#[bit_order(msb0)]` and `#[byte_order(be)]` on the
+/// struct switch either or both, e.g. to match a protocol or hardware
+/// register that numbers bits MSB-first or transmits bytes big-endian.
+///
+/// An opt-in `#[secret]` on the struct additionally derives `Zeroize` and
+/// `Drop` so the decoded fields are wiped when the value goes out of
+/// scope. It requires the crate's `secret` feature (which pulls in the
+/// `zeroize` dependency) — without it, the derive emits a compile error
+/// instead of silently skipping the wipe.
+///
+/// This is independent from `#[secret]` on an `AsBitMaskEnum` field's
+/// type: the struct-level attribute only governs zeroize-on-drop for the
+/// struct's own fields (the arrays returned by `as_bytes` are not wiped), while the enum-level attribute only governs whether that
+/// field's own `to_bits`/`from_bits` are branchless. Sensitive data packed
+/// behind a bit-enum field generally wants both: `#[secret]` on the struct
+/// *and* on every such field's enum type.
+///
+/// Generic structs aren't supported:
+/// ```compile_fail
+/// use crate::as_bit_mask_derive::AsBitMask;
+///
+/// #[derive(AsBitMask)]
+/// pub struct Wrapper<T> {
+///     flag: bool,
+///     _marker: std::marker::PhantomData<T>,
+/// }
+/// ```
+#[proc_macro_derive(AsBitMask, attributes(bits, byte_aligned, bit_order, byte_order, secret))]
 pub fn derive_as_bit_mask(input: TokenStream) -> TokenStream {
-    // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
+    derive_as_bit_mask_impl(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
 
-    // Get the name of the struct
+fn derive_as_bit_mask_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let struct_name = &input.ident;
+    let fields = require_named_struct(input, "AsBitMask")?;
 
-    // Extract fields from the struct
-    let fields = match &input.data {
-        Data::Struct(data_struct) => {
-            match &data_struct.fields {
-                Fields::Named(fields_named) => fields_named,
-                _ => panic!("AsBitMask derive only supports structs with named fields"),
-            }
-        },
-        _ => panic!("AsBitMask derive only supports structs"),
-    };
-
-    // Collect field names
-    let mut field_names = Vec::new();
+    // Collect fields along with their bit width and running bit offset
+    let mut packed_fields = Vec::new();
+    let mut bit_offset = 0usize;
     for field in &fields.named {
-        if let Some(ident) = &field.ident {
-            field_names.push(ident);
-        }
+        let Some(ident) = &field.ident else { continue };
+        let width = field_width(field)?;
+        packed_fields.push((PackedField {
+            ident,
+            ty: &field.ty,
+            width,
+            kind: field_kind(&field.ty),
+        }, bit_offset));
+        bit_offset += width;
     }
 
     // Calculate number of bytes needed
-    let num_fields = field_names.len();
-    let num_bytes = (num_fields + 7) / 8; // Ceiling division by 8
-
-    // Generate the expressions for as_bytes method
-    let mut as_bytes_expressions = Vec::new();
-    for byte_index in 0..num_bytes {
-        let mut byte_expr = Vec::new();
-        for bit_pos in 0usize..8 {
-            let field_index = byte_index * 8 + bit_pos;
-            if field_index < num_fields {
-                let field = &field_names[field_index];
-                byte_expr.push(quote! {
-                    ((self.#field as u8) << #bit_pos)
-                });
-            }
+    let total_bits = bit_offset;
+    let num_bytes = (total_bits + 7) / 8; // Ceiling division by 8
+
+    check_byte_aligned(input, total_bits)?;
+
+    let bit_order = parse_bit_order(&input.attrs)?;
+    let byte_order = parse_byte_order(&input.attrs)?;
+
+    // Generate the expressions for as_bytes method: for each field, OR its
+    // bits into whichever byte(s) they land in.
+    let mut byte_terms: Vec<Vec<proc_macro2::TokenStream>> = vec![Vec::new(); num_bytes];
+    for (field, offset) in &packed_fields {
+        let ident = field.ident;
+        let raw = match field.kind {
+            FieldKind::Bool => quote! { (self.#ident as u8) },
+            FieldKind::Integer => quote! { self.#ident },
+            FieldKind::BitEnum => quote! { self.#ident.to_bits() },
+        };
+        for k in 0..field.width {
+            let bit = offset + k;
+            let (byte_index, bit_pos) = physical_location(bit, num_bytes, &bit_order, &byte_order);
+            let term = quote! { ((((#raw >> #k) & 1) as u8) << #bit_pos) };
+            byte_terms[byte_index].push(term);
         }
+    }
 
-        if !byte_expr.is_empty() {
-            as_bytes_expressions.push(quote! {
-                #(#byte_expr)|*
-            });
+    let as_bytes_expressions: Vec<_> = byte_terms.into_iter().map(|terms| {
+        if terms.is_empty() {
+            quote! { 0 }
         } else {
-            as_bytes_expressions.push(quote! { 0 });
+            quote! { #(#terms)|* }
         }
-    }
+    }).collect();
 
     // Generate the field initializers for from_bytes method
-    let mut from_bytes_initializers = Vec::new();
-    for (field_index, field) in field_names.iter().enumerate() {
-        let byte_index = field_index / 8;
-        let bit_pos: usize = field_index % 8;
+    let from_bytes_initializers = packed_fields.iter().map(|(field, offset)| {
+        let ident = field.ident;
+        let ty = field.ty;
 
-        from_bytes_initializers.push(quote! {
-            #field: (bytes[#byte_index] & (1 << #bit_pos as usize)) != 0
-        });
-    }
+        match field.kind {
+            FieldKind::Bool => {
+                let (byte_index, bit_pos) = physical_location(*offset, num_bytes, &bit_order, &byte_order);
+                quote! {
+                    #ident: (bytes[#byte_index] >> #bit_pos) & 1 != 0
+                }
+            },
+            FieldKind::Integer => {
+                let assigns = (0..field.width).map(|k| {
+                    let bit = offset + k;
+                    let (byte_index, bit_pos) = physical_location(bit, num_bytes, &bit_order, &byte_order);
+                    quote! {
+                        v |= (((bytes[#byte_index] >> #bit_pos) & 1) as #ty) << #k;
+                    }
+                });
+                quote! {
+                    #ident: {
+                        let mut v: #ty = 0;
+                        #(#assigns)*
+                        v
+                    }
+                }
+            },
+            FieldKind::BitEnum => {
+                let raw_ty = uint_type_for_width(field.width);
+                let assigns = (0..field.width).map(|k| {
+                    let bit = offset + k;
+                    let (byte_index, bit_pos) = physical_location(bit, num_bytes, &bit_order, &byte_order);
+                    quote! {
+                        v |= (((bytes[#byte_index] >> #bit_pos) & 1) as #raw_ty) << #k;
+                    }
+                });
+                quote! {
+                    #ident: {
+                        let mut v: #raw_ty = 0;
+                        #(#assigns)*
+                        #ty::from_bits(v)
+                    }
+                }
+            },
+        }
+    });
+
+    // An enum field's #[bits(n)] must reserve exactly as many bits as the
+    // enum's own `BITS` constant, or the high bits of `to_bits()` would be
+    // silently dropped by the packing loop above, and `from_bits` would
+    // never see them set. Catch the mismatch at compile time instead.
+    let enum_width_assertions = packed_fields.iter().filter_map(|(field, _)| {
+        if field.kind != FieldKind::BitEnum {
+            return None;
+        }
+        let ty = field.ty;
+        let width = field.width;
+        Some(quote! {
+            const _: () = assert!(
+                #width == <#ty>::BITS,
+                "#[bits(n)] on an enum field must equal the enum's BITS constant",
+            );
+        })
+    });
+
+    // When `#[secret]` is set, also wipe the struct's fields on drop. This
+    // needs the `secret` feature (and its `zeroize` dependency); without
+    // it, fail the build instead of quietly skipping the wipe.
+    let secret_impls = if has_unit_attr(&input.attrs, "secret") {
+        quote! {
+            #[cfg(not(feature = "secret"))]
+            compile_error!("#[secret] requires the `secret` cargo feature to be enabled");
+
+            #[cfg(feature = "secret")]
+            impl zeroize::Zeroize for #struct_name {
+                fn zeroize(&mut self) {
+                    // Can't do `*self = zero`: that would drop the current
+                    // value in place first, re-entering this type's `Drop`
+                    // impl (which calls back into `zeroize`) forever. Write
+                    // the zeroed value directly over `self` instead, which
+                    // runs neither the old nor the new value's destructor.
+                    //
+                    // The write must be volatile, with a compiler fence
+                    // after it, or the optimizer can prove the store is
+                    // dead (nothing reads `self` again before it's dropped)
+                    // and remove it entirely in release builds — exactly
+                    // what `zeroize` upstream guards against.
+                    let zero = <Self as AsBitMask<#num_bytes>>::from_bytes(&[0u8; #num_bytes]);
+                    unsafe {
+                        core::ptr::write_volatile(self, zero);
+                    }
+                    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+                }
+            }
+
+            #[cfg(feature = "secret")]
+            impl Drop for #struct_name {
+                fn drop(&mut self) {
+                    zeroize::Zeroize::zeroize(self);
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
 
     // Generate the implementation
-    let expanded = quote! {
+    Ok(quote! {
         impl AsBitMask<#num_bytes> for #struct_name {
             fn as_bytes(&self) -> [u8; #num_bytes] {
                 [#(#as_bytes_expressions),*]
@@ -89,13 +515,12 @@ pub fn derive_as_bit_mask(input: TokenStream) -> TokenStream {
                 }
             }
         }
-    };
-
-    // Return the generated implementation as a token stream
-    expanded.into()
-}
 
+        #(#enum_width_assertions)*
 
+        #secret_impls
+    })
+}
 
 /// Automatically implements the AsBitMask trait for structs with boolean fields.
 ///
@@ -105,6 +530,15 @@ pub fn derive_as_bit_mask(input: TokenStream) -> TokenStream {
 ///
 /// The number of bytes in the array is calculated based on the number of fields.
 ///
+/// An opt-in `#[byte_aligned]` on the struct rejects a layout that doesn't
+/// fill its bytes exactly (i.e. `max_index + 1` is not a multiple of 8),
+/// instead of silently padding with dead bits.
+///
+/// By default bits are numbered lsb0-within-byte and bytes are laid out
+/// little-endian. `#[bit_order(msb0)]` and `#[byte_order(be)]` on the
+/// struct switch either or both. `#[index(n)]` always refers to the
+/// logical bit number, before this ordering transform is applied.
+///
 /// Example:
 /// ```no_run
 /// use crate::as_bit_mask_derive::AsBitMaskExplicit;
@@ -125,43 +559,31 @@ pub fn derive_as_bit_mask(input: TokenStream) -> TokenStream {
 ///
 ///
 /// ```
-#[proc_macro_derive(AsBitMaskExplicit, attributes(index))]
+///
+/// Two fields can't claim the same bit:
+/// ```compile_fail
+/// use crate::as_bit_mask_derive::AsBitMaskExplicit;
+///
+/// #[derive(AsBitMaskExplicit)]
+/// pub struct Colliding {
+///     #[index(0)]
+///     a: bool,
+///     #[index(0)]
+///     b: bool,
+/// }
+/// ```
+#[proc_macro_derive(AsBitMaskExplicit, attributes(index, byte_aligned, bit_order, byte_order))]
 pub fn derive_as_bit_mask_explicit(input: TokenStream) -> TokenStream {
-    // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
+    derive_as_bit_mask_explicit_impl(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
 
-    // Get the name of the struct
+fn derive_as_bit_mask_explicit_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let struct_name = &input.ident;
-
-    // Extract fields from the struct
-    let fields = match &input.data {
-        Data::Struct(data_struct) => {
-            match &data_struct.fields {
-                Fields::Named(fields_named) => fields_named,
-                _ => panic!("AsBitMaskExplicit derive only supports structs with named fields"),
-            }
-        },
-        _ => panic!("AsBitMaskExplicit derive only supports structs"),
-    };
-
-    // Collect field names and their explicit indices
-    let mut field_data = Vec::new();
-    for field in &fields.named {
-        if let Some(ident) = &field.ident {
-            // Look for the #[index(n)] attribute
-            let mut index = None;
-            for attr in &field.attrs {
-                if attr.path().is_ident("index") {
-                    // Parse the index value from the attribute
-                    let index_value = attr.parse_args::<syn::LitInt>().expect("Index must be an integer");
-                    index = Some(index_value.base10_parse::<usize>().expect("Failed to parse index as usize"));
-                }
-            }
-
-            let idx = index.expect(&format!("Field '{}' is missing #[index(n)] attribute", ident));
-            field_data.push((ident, idx));
-        }
-    }
+    let fields = require_named_struct(input, "AsBitMaskExplicit")?;
+    let field_data = collect_indexed_fields(fields)?;
 
     // Find the maximum bit index to determine the required number of bytes
     let max_index = field_data.iter()
@@ -171,37 +593,32 @@ pub fn derive_as_bit_mask_explicit(input: TokenStream) -> TokenStream {
 
     let num_bytes = (max_index + 8) / 8; // Ceiling division by 8
 
-    // Generate the expressions for as_bytes method
-    let mut as_bytes_expressions = Vec::new();
-    for byte_index in 0..num_bytes {
-        let byte_start = byte_index * 8;
-        let byte_end = byte_start + 7;
+    check_byte_aligned(input, max_index + 1)?;
 
-        // Collect fields that belong to this byte
-        let byte_fields: Vec<_> = field_data.iter()
-            .filter(|(_, idx)| *idx >= byte_start && *idx <= byte_end)
-            .collect();
+    let bit_order = parse_bit_order(&input.attrs)?;
+    let byte_order = parse_byte_order(&input.attrs)?;
 
-        if byte_fields.is_empty() {
-            as_bytes_expressions.push(quote! { 0 });
-        } else {
-            let field_expressions = byte_fields.iter().map(|(field, idx)| {
-                let bit_pos = idx % 8;
-                quote! {
-                    ((self.#field as u8) << #bit_pos)
-                }
-            });
+    // Generate the expressions for as_bytes method: for each field, OR its
+    // bit into whichever physical byte it lands in.
+    let mut byte_terms: Vec<Vec<proc_macro2::TokenStream>> = vec![Vec::new(); num_bytes];
+    for (field, idx) in &field_data {
+        let (byte_index, bit_pos) = physical_location(*idx, num_bytes, &bit_order, &byte_order);
+        byte_terms[byte_index].push(quote! {
+            ((self.#field as u8) << #bit_pos)
+        });
+    }
 
-            as_bytes_expressions.push(quote! {
-                #(#field_expressions)|*
-            });
+    let as_bytes_expressions: Vec<_> = byte_terms.into_iter().map(|terms| {
+        if terms.is_empty() {
+            quote! { 0 }
+        } else {
+            quote! { #(#terms)|* }
         }
-    }
+    }).collect();
 
     // Generate the field initializers for from_bytes method
     let from_bytes_initializers = field_data.iter().map(|(field, idx)| {
-        let byte_index = idx / 8;
-        let bit_pos = idx % 8;
+        let (byte_index, bit_pos) = physical_location(*idx, num_bytes, &bit_order, &byte_order);
 
         quote! {
             #field: (bytes[#byte_index] & (1 << #bit_pos)) != 0
@@ -209,7 +626,7 @@ pub fn derive_as_bit_mask_explicit(input: TokenStream) -> TokenStream {
     });
 
     // Generate the implementation
-    let expanded = quote! {
+    Ok(quote! {
         impl AsBitMask<#num_bytes> for #struct_name {
             fn as_bytes(&self) -> [u8; #num_bytes] {
                 [#(#as_bytes_expressions),*]
@@ -221,14 +638,9 @@ pub fn derive_as_bit_mask_explicit(input: TokenStream) -> TokenStream {
                 }
             }
         }
-    };
-
-    // Return the generated implementation as a token stream
-    expanded.into()
+    })
 }
 
-
-
 /// Automatically implements the AsBits trait for structs with boolean fields.
 ///
 /// This macro will generate implementations for:
@@ -236,24 +648,26 @@ pub fn derive_as_bit_mask_explicit(input: TokenStream) -> TokenStream {
 /// - `from_bits`: Constructs the struct from a boolean array representation
 ///
 /// The length of the array is calculated based on the number of fields.
+///
+/// Only structs with named fields are supported — tuple structs are
+/// rejected:
+/// ```compile_fail
+/// use crate::as_bit_mask_derive::AsBits;
+///
+/// #[derive(AsBits)]
+/// pub struct Tuple(bool, bool);
+/// ```
 #[proc_macro_derive(AsBits)]
 pub fn derive_as_bits(input: TokenStream) -> TokenStream {
-    // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
+    derive_as_bits_impl(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
 
-    // Get the name of the struct
+fn derive_as_bits_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let struct_name = &input.ident;
-
-    // Extract fields from the struct
-    let fields = match &input.data {
-        Data::Struct(data_struct) => {
-            match &data_struct.fields {
-                Fields::Named(fields_named) => fields_named,
-                _ => panic!("AsBits derive only supports structs with named fields"),
-            }
-        },
-        _ => panic!("AsBits derive only supports structs"),
-    };
+    let fields = require_named_struct(input, "AsBits")?;
 
     // Collect field names
     let mut field_names = Vec::new();
@@ -277,7 +691,7 @@ pub fn derive_as_bits(input: TokenStream) -> TokenStream {
     });
 
     // Generate the implementation
-    let expanded = quote! {
+    Ok(quote! {
         impl AsBits<#num_fields> for #struct_name {
             fn as_bits(&self) -> [bool; #num_fields] {
                 [#(#as_bits_expressions),*]
@@ -289,10 +703,7 @@ pub fn derive_as_bits(input: TokenStream) -> TokenStream {
                 }
             }
         }
-    };
-
-    // Return the generated implementation as a token stream
-    expanded.into()
+    })
 }
 
 /// Automatically implements the AsBits trait for structs with boolean fields.
@@ -320,52 +731,40 @@ pub fn derive_as_bits(input: TokenStream) -> TokenStream {
 ///     e: bool,
 /// }
 /// ```
+///
+/// An `#[index(n)]` beyond `#[total_bits(n)]` is rejected:
+/// ```compile_fail
+/// use crate::as_bit_mask_derive::AsBitsExplicit;
+///
+/// #[derive(AsBitsExplicit)]
+/// #[total_bits(4)]
+/// pub struct OutOfBounds {
+///     #[index(7)]
+///     a: bool,
+/// }
+/// ```
 #[proc_macro_derive(AsBitsExplicit, attributes(index, total_bits))]
 pub fn derive_as_bits_explicit(input: TokenStream) -> TokenStream {
-    // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
+    derive_as_bits_explicit_impl(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
 
-    // Get the name of the struct
+fn derive_as_bits_explicit_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let struct_name = &input.ident;
 
     // Look for the #[total_bits(n)] attribute
     let mut total_bits = None;
     for attr in &input.attrs {
         if attr.path().is_ident("total_bits") {
-            let value = attr.parse_args::<syn::LitInt>().expect("total_bits must be an integer");
-            total_bits = Some(value.base10_parse::<usize>().expect("Failed to parse total_bits as usize"));
+            let value = attr.parse_args::<syn::LitInt>()?;
+            total_bits = Some(value.base10_parse::<usize>()?);
         }
     }
 
-    // Extract fields from the struct
-    let fields = match &input.data {
-        Data::Struct(data_struct) => {
-            match &data_struct.fields {
-                Fields::Named(fields_named) => fields_named,
-                _ => panic!("AsBitsExplicit derive only supports structs with named fields"),
-            }
-        },
-        _ => panic!("AsBitsExplicit derive only supports structs"),
-    };
-
-    // Collect field names and their explicit indices
-    let mut field_data = Vec::new();
-    for field in &fields.named {
-        if let Some(ident) = &field.ident {
-            // Look for the #[index(n)] attribute
-            let mut index = None;
-            for attr in &field.attrs {
-                if attr.path().is_ident("index") {
-                    // Parse the index value from the attribute
-                    let index_value = attr.parse_args::<syn::LitInt>().expect("Index must be an integer");
-                    index = Some(index_value.base10_parse::<usize>().expect("Failed to parse index as usize"));
-                }
-            }
-
-            let idx = index.expect(&format!("Field '{}' is missing #[index(n)] attribute", ident));
-            field_data.push((ident, idx));
-        }
-    }
+    let fields = require_named_struct(input, "AsBitsExplicit")?;
+    let field_data = collect_indexed_fields(fields)?;
 
     // Find the maximum bit index
     let max_index = field_data.iter()
@@ -376,10 +775,17 @@ pub fn derive_as_bits_explicit(input: TokenStream) -> TokenStream {
     // Determine the array size (either from #[total_bits] or based on max_index)
     let array_size = total_bits.unwrap_or(max_index + 1);
 
-    // Make sure the array size is sufficient for all fields
-    if array_size <= max_index {
-        panic!("total_bits value ({}) is too small for the maximum field index ({})",
-               array_size, max_index);
+    // Make sure every field's index actually fits in the array
+    for (ident, idx) in &field_data {
+        if *idx >= array_size {
+            return Err(Error::new_spanned(
+                *ident,
+                format!(
+                    "index {} is out of bounds for total_bits({})",
+                    idx, array_size
+                ),
+            ));
+        }
     }
 
     // Generate the expressions for as_bits method
@@ -400,7 +806,7 @@ pub fn derive_as_bits_explicit(input: TokenStream) -> TokenStream {
     });
 
     // Generate the implementation
-    let expanded = quote! {
+    Ok(quote! {
         impl AsBits<#array_size> for #struct_name {
             fn as_bits(&self) -> [bool; #array_size] {
                 [#(#as_bits_expressions),*]
@@ -412,8 +818,193 @@ pub fn derive_as_bits_explicit(input: TokenStream) -> TokenStream {
                 }
             }
         }
+    })
+}
+
+/// Lets a fieldless enum act as a multi-bit field in an `AsBitMask` struct.
+///
+/// The enum must have exactly `2^k` variants, each with an explicit
+/// discriminant, so that every possible `k`-bit pattern maps to a variant.
+/// This generates:
+/// - `BITS`: the number of bits (`k`) the enum occupies
+/// - `to_bits`: the variant's discriminant as an unsigned integer
+/// - `from_bits`: the reverse lookup, panicking on a value with no matching
+///   discriminant
+///
+/// An opt-in `#[secret]` on the enum trades the match/if-chain above for a
+/// branchless encoding: `to_bits` casts `self` directly, and `from_bits`
+/// indexes a fixed array of all variants by the raw value, so neither
+/// direction branches on the (potentially sensitive) bit pattern. This
+/// requires the enum to derive `Clone, Copy`, and its discriminants to run
+/// `0, 1, 2, ...` in declaration order so the raw value is already a valid
+/// index.
+///
+/// Example:
+/// ```no_run
+/// use crate::as_bit_mask_derive::AsBitMaskEnum;
+///
+/// #[derive(AsBitMaskEnum, Clone, Copy)]
+/// pub enum Mode {
+///     Idle = 0,
+///     Running = 1,
+///     Paused = 2,
+///     Stopped = 3,
+/// }
+/// ```
+///
+/// A discriminant outside `0..2^BITS` is rejected, even with a
+/// power-of-two variant count:
+/// ```compile_fail
+/// use crate::as_bit_mask_derive::AsBitMaskEnum;
+///
+/// #[derive(AsBitMaskEnum, Clone, Copy)]
+/// pub enum Mode {
+///     Idle = 0,
+///     Running = 1,
+///     Paused = 2,
+///     Stopped = 7,
+/// }
+/// ```
+#[proc_macro_derive(AsBitMaskEnum, attributes(secret))]
+pub fn derive_as_bit_mask_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive_as_bit_mask_enum_impl(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn derive_as_bit_mask_enum_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let enum_name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "AsBitMaskEnum derive does not support generic parameters",
+        ));
+    }
+
+    let data_enum = match &input.data {
+        Data::Enum(data_enum) => data_enum,
+        _ => return Err(Error::new_spanned(&input.ident, "AsBitMaskEnum derive only supports enums")),
+    };
+
+    let mut variant_idents = Vec::new();
+    for variant in &data_enum.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                format!("AsBitMaskEnum derive only supports fieldless variants, '{}' has fields", variant.ident),
+            ));
+        }
+        if variant.discriminant.is_none() {
+            return Err(Error::new_spanned(
+                variant,
+                format!("AsBitMaskEnum derive requires an explicit discriminant on variant '{}'", variant.ident),
+            ));
+        }
+        variant_idents.push(&variant.ident);
+    }
+
+    let num_variants = variant_idents.len();
+    if !num_variants.is_power_of_two() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            format!(
+                "AsBitMaskEnum derive requires a power-of-two number of variants, '{}' has {}",
+                enum_name, num_variants
+            ),
+        ));
+    }
+    let bits = num_variants.trailing_zeros() as usize;
+    let raw_ty = uint_type_for_width(bits);
+
+    // A power-of-two variant count alone doesn't guarantee every k-bit
+    // pattern maps to a variant: discriminants are whatever the user wrote,
+    // and could skip values or run outside 0..2^BITS (e.g. 0, 1, 2, 7 for 4
+    // variants). Without this, an in-range packed value with no matching
+    // discriminant reaches `from_bits`'s panic at runtime instead of being
+    // caught here at compile time.
+    let discriminant_range_checks = variant_idents.iter().map(|ident| {
+        quote! {
+            const _: () = assert!(
+                (#enum_name::#ident as #raw_ty as usize) < #num_variants,
+                "AsBitMaskEnum variant discriminants must all be within 0..2^BITS",
+            );
+        }
+    });
+
+    let (to_bits_body, from_bits_body) = if has_unit_attr(&input.attrs, "secret") {
+        // Every variant's discriminant must equal its declaration position,
+        // so the raw bits are already a valid array index: no match/if on
+        // the secret value is needed to go either direction.
+        for (position, variant) in data_enum.variants.iter().enumerate() {
+            let (_, disc_expr) = variant.discriminant.as_ref().unwrap();
+            let disc_lit = match disc_expr {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(lit_int), .. }) => lit_int,
+                _ => return Err(Error::new_spanned(
+                    disc_expr,
+                    "#[secret] requires a literal integer discriminant",
+                )),
+            };
+            let disc_value: usize = disc_lit.base10_parse()?;
+            if disc_value != position {
+                return Err(Error::new_spanned(
+                    disc_expr,
+                    format!(
+                        "#[secret] requires discriminants in declaration order starting at 0 (variant '{}' expected {}, got {})",
+                        variant.ident, position, disc_value
+                    ),
+                ));
+            }
+        }
+
+        let variants = variant_idents.iter().map(|ident| quote! { #enum_name::#ident });
+        (
+            quote! { *self as #raw_ty },
+            quote! {
+                const VARIANTS: [#enum_name; #num_variants] = [#(#variants),*];
+                VARIANTS[value as usize]
+            },
+        )
+    } else {
+        let to_bits_arms = variant_idents.iter().map(|ident| {
+            quote! { #enum_name::#ident => #enum_name::#ident as #raw_ty }
+        });
+
+        let from_bits_branches = variant_idents.iter().map(|ident| {
+            quote! {
+                if value == (#enum_name::#ident as #raw_ty) {
+                    return #enum_name::#ident;
+                }
+            }
+        });
+
+        (
+            quote! {
+                match self {
+                    #(#to_bits_arms,)*
+                }
+            },
+            quote! {
+                #(#from_bits_branches)*
+                panic!("{} is not a valid bit pattern for {}", value, stringify!(#enum_name));
+            },
+        )
     };
 
-    // Return the generated implementation as a token stream
-    expanded.into()
+    Ok(quote! {
+        #(#discriminant_range_checks)*
+
+        impl #enum_name {
+            pub const BITS: usize = #bits;
+
+            pub fn to_bits(&self) -> #raw_ty {
+                #to_bits_body
+            }
+
+            pub fn from_bits(value: #raw_ty) -> Self {
+                #from_bits_body
+            }
+        }
+    })
 }